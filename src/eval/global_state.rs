@@ -29,6 +29,14 @@ pub struct GlobalRuntimeState {
     /// Stack of imported [modules][crate::Module].
     #[cfg(not(feature = "no_module"))]
     modules: StaticVec<SharedModule>,
+    /// Acceleration index mapping an import name to the stack positions (in `imports`) at
+    /// which it is bound, most-recent last.
+    ///
+    /// This is a pure acceleration structure kept in sync by [`push_import`][Self::push_import],
+    /// [`truncate_imports`][Self::truncate_imports] and `Extend`; it must always agree with
+    /// `imports`.
+    #[cfg(not(feature = "no_module"))]
+    imports_index: std::collections::BTreeMap<ImmutableString, StaticVec<usize>>,
     /// The current stack of loaded [modules][Module].
     pub lib: StaticVec<SharedModule>,
     /// Source of the current context.
@@ -37,6 +45,17 @@ pub struct GlobalRuntimeState {
     pub source: Option<ImmutableString>,
     /// Number of operations performed.
     pub num_operations: u64,
+    /// Stack of per-level operation counters, tallying operations performed at each nesting
+    /// level of function calls, pushed/popped by [`enter_level`][Self::enter_level] and
+    /// [`exit_level`][Self::exit_level] as frames enter and leave.
+    ///
+    /// `level_operations.len() == level + 1` holds as long as `level` is changed only through
+    /// `enter_level`/`exit_level` or [`restore`][Self::restore], with index `0` present for the
+    /// root level. Writing the public `level` field directly does not touch this stack.
+    ///
+    /// Only maintained under the `operation_profiling` feature.
+    #[cfg(feature = "operation_profiling")]
+    level_operations: StaticVec<u64>,
     /// Number of modules loaded.
     #[cfg(not(feature = "no_module"))]
     pub num_modules_loaded: usize,
@@ -86,9 +105,19 @@ impl GlobalRuntimeState {
             imports: StaticVec::new_const(),
             #[cfg(not(feature = "no_module"))]
             modules: StaticVec::new_const(),
+            #[cfg(not(feature = "no_module"))]
+            imports_index: std::collections::BTreeMap::new(),
             lib: StaticVec::new_const(),
             source: None,
             num_operations: 0,
+            // The root (level 0) frame always exists, so `level_operations.len()` stays in
+            // lock-step with `level + 1` as `enter_level`/`exit_level` push and pop deeper frames.
+            #[cfg(feature = "operation_profiling")]
+            level_operations: {
+                let mut levels = StaticVec::new_const();
+                levels.push(0);
+                levels
+            },
             #[cfg(not(feature = "no_module"))]
             num_modules_loaded: 0,
             scope_level: 0,
@@ -149,16 +178,18 @@ impl GlobalRuntimeState {
     }
     /// Get the index of a globally-imported [module][crate::Module] by name.
     ///
+    /// This is resolved via the `imports_index` acceleration map in near-constant time, rather
+    /// than by scanning the `imports` stack.
+    ///
     /// Not available under `no_module`.
     #[cfg(not(feature = "no_module"))]
     #[inline]
     #[must_use]
     pub fn find_import(&self, name: &str) -> Option<usize> {
-        self.imports
-            .iter()
-            .rev()
-            .position(|key| key.as_str() == name)
-            .map(|i| self.imports.len() - 1 - i)
+        self.imports_index
+            .get(name)
+            .and_then(|positions| positions.last())
+            .copied()
     }
     /// Push an imported [module][crate::Module] onto the stack.
     ///
@@ -170,17 +201,30 @@ impl GlobalRuntimeState {
         name: impl Into<ImmutableString>,
         module: impl Into<SharedModule>,
     ) {
-        self.imports.push(name.into());
+        let name = name.into();
+        let index = self.imports.len();
+        self.imports.push(name.clone());
         self.modules.push(module.into());
+        self.imports_index
+            .entry(name)
+            .or_insert_with(StaticVec::new_const)
+            .push(index);
     }
     /// Truncate the stack of globally-imported [modules][crate::Module] to a particular length.
     ///
     /// Not available under `no_module`.
     #[cfg(not(feature = "no_module"))]
-    #[inline(always)]
+    #[inline]
     pub fn truncate_imports(&mut self, size: usize) {
         self.imports.truncate(size);
         self.modules.truncate(size);
+
+        self.imports_index.retain(|_, positions| {
+            while matches!(positions.last(), Some(&i) if i >= size) {
+                positions.pop();
+            }
+            !positions.is_empty()
+        });
     }
     /// Get an iterator to the stack of globally-imported [modules][crate::Module] in reverse order.
     ///
@@ -212,6 +256,52 @@ impl GlobalRuntimeState {
     pub fn scan_imports_raw(&self) -> impl Iterator<Item = (&ImmutableString, &SharedModule)> {
         self.imports.iter().zip(self.modules.iter())
     }
+    /// Get an iterator over the globally-defined constants, if any.
+    ///
+    /// This takes a snapshot of the constants cache, since it is shared and interior-mutable, and
+    /// so clones every name and value up front. This differs from the borrowing iterator returned
+    /// by [`iter_imports`][Self::iter_imports], which this method is otherwise meant to mirror.
+    ///
+    /// Not available under `no_module` or `no_function`.
+    #[cfg(not(feature = "no_module"))]
+    #[cfg(not(feature = "no_function"))]
+    #[inline]
+    pub fn iter_constants(&self) -> impl Iterator<Item = (ImmutableString, Dynamic)> {
+        self.constants
+            .as_ref()
+            .map(|c| {
+                #[cfg(not(feature = "sync"))]
+                let c = c.borrow();
+                #[cfg(feature = "sync")]
+                let c = c.read().unwrap();
+
+                c.iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter()
+    }
+    /// Set the value of a globally-defined constant, lazily initializing the constants cache if
+    /// it does not yet exist.
+    ///
+    /// This allows embedders to seed compile-time-style constants programmatically, in addition
+    /// to introspecting those defined by scripts via [`iter_constants`][Self::iter_constants].
+    ///
+    /// Not available under `no_module` or `no_function`.
+    #[cfg(not(feature = "no_module"))]
+    #[cfg(not(feature = "no_function"))]
+    #[inline]
+    pub fn set_constant(&mut self, name: impl Into<ImmutableString>, value: impl Into<Dynamic>) {
+        let constants = self.constants.get_or_insert_with(|| {
+            crate::Shared::new(crate::Locked::new(std::collections::BTreeMap::new()))
+        });
+
+        #[cfg(not(feature = "sync"))]
+        constants.borrow_mut().insert(name.into(), value.into());
+        #[cfg(feature = "sync")]
+        constants.write().unwrap().insert(name.into(), value.into());
+    }
     /// Can the particular function with [`Dynamic`] parameter(s) exist in the stack of
     /// globally-imported [modules][crate::Module]?
     ///
@@ -287,6 +377,59 @@ impl GlobalRuntimeState {
     pub(crate) const fn source_raw(&self) -> Option<&ImmutableString> {
         self.source.as_ref()
     }
+    /// Enter a new function-call nesting level, incrementing [`level`][Self::level] and (under
+    /// `operation_profiling`) pushing a fresh per-level operation-counting frame.
+    ///
+    /// Call sites that increment `level` to enter a function call or imported module should go
+    /// through this method, paired with [`exit_level`][Self::exit_level], rather than mutating
+    /// `level` directly, so that the operation-counting frame stack stays in lock-step with the
+    /// actual call-stack depth.
+    #[inline]
+    pub(crate) fn enter_level(&mut self) {
+        self.level += 1;
+        #[cfg(feature = "operation_profiling")]
+        self.level_operations.push(0);
+    }
+    /// Leave the current function-call nesting level, decrementing [`level`][Self::level] and
+    /// (under `operation_profiling`) popping the per-level operation-counting frame pushed by the
+    /// matching [`enter_level`][Self::enter_level].
+    #[inline]
+    pub(crate) fn exit_level(&mut self) {
+        #[cfg(feature = "operation_profiling")]
+        self.level_operations.pop();
+        self.level -= 1;
+    }
+    /// Record that one operation has been performed, bumping [`num_operations`][Self::num_operations]
+    /// and, under `operation_profiling`, tallying it against the frame for the current nesting
+    /// level.
+    ///
+    /// Call sites that currently do `global.num_operations += 1` should call this method instead,
+    /// so per-level accounting does not silently drift out of sync with the global counter.
+    #[inline(always)]
+    pub(crate) fn count_operation(&mut self) {
+        self.num_operations += 1;
+
+        #[cfg(feature = "operation_profiling")]
+        if let Some(count) = self.level_operations.last_mut() {
+            *count += 1;
+        }
+    }
+    /// Get the number of operations tallied at a particular nesting `level`.
+    ///
+    /// This allows hosts to attribute operation cost to a particular imported module or
+    /// function-call frame, rather than only seeing a single global ceiling.
+    ///
+    /// Only valid for a level that is currently on the call stack, i.e. `0 <= level <=
+    /// self.level`; a level that has not yet been entered, or has already been left, reads as
+    /// `0`.
+    ///
+    /// Only available under the `operation_profiling` feature.
+    #[cfg(feature = "operation_profiling")]
+    #[inline]
+    #[must_use]
+    pub fn operations_at_level(&self, level: usize) -> u64 {
+        self.level_operations.get(level).copied().unwrap_or(0)
+    }
     /// Get the pre-calculated index getter hash.
     #[cfg(any(not(feature = "no_index"), not(feature = "no_object")))]
     #[must_use]
@@ -313,6 +456,124 @@ impl GlobalRuntimeState {
             self.fn_hash_indexing.1
         }
     }
+    /// Create a snapshot checkpoint of the current state.
+    ///
+    /// This can later be passed to [`restore`][Self::restore] to cheaply roll the state back,
+    /// e.g. to unwind imported modules and counters after running a script fragment that errored
+    /// or was rejected, without rebuilding the whole engine context.
+    ///
+    /// The contents of the constants cache are copied out, rather than just cloning the shared
+    /// handle, so that [`restore`][Self::restore] can undo constants set or overwritten after
+    /// this snapshot was taken. Likewise, under `operation_profiling`, the per-level operation
+    /// counters are copied by value so that `restore` can undo operations counted after this
+    /// snapshot, not just restore the stack depth.
+    #[inline]
+    #[must_use]
+    pub fn snapshot(&self) -> GlobalRuntimeStateSnapshot {
+        GlobalRuntimeStateSnapshot {
+            #[cfg(not(feature = "no_module"))]
+            imports_len: self.imports.len(),
+            #[cfg(not(feature = "no_module"))]
+            num_modules_loaded: self.num_modules_loaded,
+            source: self.source.clone(),
+            num_operations: self.num_operations,
+            #[cfg(feature = "operation_profiling")]
+            level_operations: self.level_operations.clone(),
+            level: self.level,
+            scope_level: self.scope_level,
+            always_search_scope: self.always_search_scope,
+            #[cfg(not(feature = "no_module"))]
+            #[cfg(not(feature = "no_function"))]
+            constants: self.constants.as_ref().map(|c| {
+                #[cfg(not(feature = "sync"))]
+                let c = c.borrow();
+                #[cfg(feature = "sync")]
+                let c = c.read().unwrap();
+
+                c.clone()
+            }),
+        }
+    }
+    /// Restore state from a snapshot checkpoint previously returned by
+    /// [`snapshot`][Self::snapshot].
+    ///
+    /// The import stack is truncated back to the captured length rather than cloned, so this
+    /// stays allocation-light. The constants cache, if present both before and after, has its
+    /// contents overwritten in place (the shared handle is preserved) rather than being swapped
+    /// out, since other clones of [`GlobalRuntimeState`] may hold the same handle.
+    #[inline]
+    pub fn restore(&mut self, snapshot: GlobalRuntimeStateSnapshot) {
+        #[cfg(not(feature = "no_module"))]
+        {
+            self.truncate_imports(snapshot.imports_len);
+            self.num_modules_loaded = snapshot.num_modules_loaded;
+        }
+
+        self.source = snapshot.source;
+        self.num_operations = snapshot.num_operations;
+        self.level = snapshot.level;
+        self.scope_level = snapshot.scope_level;
+        self.always_search_scope = snapshot.always_search_scope;
+
+        #[cfg(feature = "operation_profiling")]
+        {
+            self.level_operations = snapshot.level_operations;
+        }
+
+        #[cfg(not(feature = "no_module"))]
+        #[cfg(not(feature = "no_function"))]
+        match (&self.constants, snapshot.constants) {
+            (Some(c), Some(map)) => {
+                #[cfg(not(feature = "sync"))]
+                {
+                    *c.borrow_mut() = map;
+                }
+                #[cfg(feature = "sync")]
+                {
+                    *c.write().unwrap() = map;
+                }
+            }
+            (Some(c), None) => {
+                #[cfg(not(feature = "sync"))]
+                c.borrow_mut().clear();
+                #[cfg(feature = "sync")]
+                c.write().unwrap().clear();
+            }
+            (None, Some(map)) => {
+                self.constants = Some(crate::Shared::new(crate::Locked::new(map)));
+            }
+            (None, None) => (),
+        }
+    }
+}
+
+/// An opaque checkpoint of a [`GlobalRuntimeState`], returned by
+/// [`GlobalRuntimeState::snapshot`] and consumed by [`GlobalRuntimeState::restore`].
+///
+/// This enables hosts to implement transactional script blocks: run a fragment, and if it errors
+/// or is rejected, cheaply unwind back to a known-good point.
+#[derive(Debug, Clone)]
+pub struct GlobalRuntimeStateSnapshot {
+    #[cfg(not(feature = "no_module"))]
+    imports_len: usize,
+    #[cfg(not(feature = "no_module"))]
+    num_modules_loaded: usize,
+    source: Option<ImmutableString>,
+    num_operations: u64,
+    /// Full copy of the per-level operation-counting stack at the time of the snapshot,
+    /// so that [`restore`][GlobalRuntimeState::restore] rolls back both the stack depth and
+    /// each frame's tally, not just the depth.
+    #[cfg(feature = "operation_profiling")]
+    level_operations: StaticVec<u64>,
+    level: usize,
+    scope_level: usize,
+    always_search_scope: bool,
+    /// Contents of the constants cache at the time of the snapshot, not a shared handle, so that
+    /// [`restore`][GlobalRuntimeState::restore] can roll back constant values rather than only
+    /// the cache's existence.
+    #[cfg(not(feature = "no_module"))]
+    #[cfg(not(feature = "no_function"))]
+    constants: Option<std::collections::BTreeMap<ImmutableString, Dynamic>>,
 }
 
 #[cfg(not(feature = "no_module"))]
@@ -320,8 +581,14 @@ impl<K: Into<ImmutableString>, M: Into<SharedModule>> Extend<(K, M)> for GlobalR
     #[inline]
     fn extend<T: IntoIterator<Item = (K, M)>>(&mut self, iter: T) {
         for (k, m) in iter {
-            self.imports.push(k.into());
+            let name = k.into();
+            let index = self.imports.len();
+            self.imports.push(name.clone());
             self.modules.push(m.into());
+            self.imports_index
+                .entry(name)
+                .or_insert_with(StaticVec::new_const)
+                .push(index);
         }
     }
 }
@@ -352,3 +619,125 @@ impl fmt::Debug for GlobalRuntimeState {
         f.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_restore_round_trip() {
+        let engine = Engine::new();
+        let mut global = GlobalRuntimeState::new(&engine);
+
+        global.num_operations = 5;
+        global.level = 2;
+        global.scope_level = 1;
+        global.always_search_scope = true;
+
+        let snapshot = global.snapshot();
+
+        global.num_operations = 99;
+        global.level = 10;
+        global.scope_level = 10;
+        global.always_search_scope = false;
+
+        global.restore(snapshot);
+
+        assert_eq!(global.num_operations, 5);
+        assert_eq!(global.level, 2);
+        assert_eq!(global.scope_level, 1);
+        assert!(global.always_search_scope);
+    }
+
+    #[cfg(not(feature = "no_module"))]
+    #[cfg(not(feature = "no_function"))]
+    #[test]
+    fn snapshot_restore_rolls_back_constants() {
+        let engine = Engine::new();
+        let mut global = GlobalRuntimeState::new(&engine);
+
+        global.set_constant("PI", 3 as crate::INT);
+        let snapshot = global.snapshot();
+
+        global.set_constant("PI", 4 as crate::INT);
+        global.set_constant("E", 2 as crate::INT);
+
+        global.restore(snapshot);
+
+        let constants: std::collections::BTreeMap<_, _> = global
+            .iter_constants()
+            .map(|(k, v)| (k.to_string(), v.as_int().unwrap()))
+            .collect();
+
+        assert_eq!(constants.get("PI"), Some(&3));
+        assert_eq!(constants.get("E"), None);
+    }
+
+    #[cfg(feature = "operation_profiling")]
+    #[test]
+    fn snapshot_restore_rolls_back_level_operations() {
+        let engine = Engine::new();
+        let mut global = GlobalRuntimeState::new(&engine);
+
+        global.enter_level();
+        global.count_operation();
+        global.count_operation();
+
+        let snapshot = global.snapshot();
+
+        // Operations counted after the snapshot, at the same level, must not survive restore.
+        global.count_operation();
+        global.count_operation();
+        global.count_operation();
+
+        global.restore(snapshot);
+
+        assert_eq!(global.num_operations, 2);
+        assert_eq!(global.operations_at_level(global.level), global.num_operations);
+    }
+
+    #[cfg(feature = "operation_profiling")]
+    #[test]
+    fn per_level_operation_counting() {
+        let engine = Engine::new();
+        let mut global = GlobalRuntimeState::new(&engine);
+
+        global.count_operation();
+        global.count_operation();
+        assert_eq!(global.operations_at_level(0), 2);
+
+        global.enter_level();
+        global.count_operation();
+        assert_eq!(global.operations_at_level(1), 1);
+        assert_eq!(global.operations_at_level(0), 2);
+
+        global.exit_level();
+        assert_eq!(global.operations_at_level(1), 0);
+        assert_eq!(global.num_operations, 3);
+    }
+
+    #[cfg(not(feature = "no_module"))]
+    #[cfg(not(feature = "no_function"))]
+    #[test]
+    fn set_and_iter_constants() {
+        let engine = Engine::new();
+        let mut global = GlobalRuntimeState::new(&engine);
+
+        global.set_constant("ANSWER", 42 as crate::INT);
+        global.set_constant("NAME", "rhai");
+
+        let mut constants: Vec<_> = global
+            .iter_constants()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        constants.sort();
+
+        assert_eq!(
+            constants,
+            vec![
+                ("ANSWER".to_string(), "42".to_string()),
+                ("NAME".to_string(), "rhai".to_string()),
+            ]
+        );
+    }
+}